@@ -0,0 +1,146 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Enforces `Settings::max_connections` and a separate pre-logon budget
+/// on the acceptor side.
+///
+/// `max_connections` bounds every accepted socket, established or not.
+/// `max_pending_logons` is a tighter, independent bound on sockets that
+/// haven't sent a valid `Logon` yet: without it, a flood of sockets that
+/// never log on could occupy the *entire* `max_connections` budget for
+/// up to `logon_timeout` each, which is exactly the slow-loris scenario
+/// admission control is meant to prevent. A legitimate reconnect storm
+/// still gets through because it quickly converts its pending-logon
+/// slots into established ones.
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionLimiter {
+    max_connections: usize,
+    max_pending_logons: usize,
+    active: Arc<AtomicUsize>,
+    pending_logons: Arc<AtomicUsize>,
+}
+
+impl ConnectionLimiter {
+    /// `0` means unlimited for either bound.
+    pub(crate) fn new(max_connections: usize, max_pending_logons: usize) -> ConnectionLimiter {
+        ConnectionLimiter {
+            max_connections,
+            max_pending_logons,
+            active: Arc::new(AtomicUsize::new(0)),
+            pending_logons: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Reserve a slot for a newly accepted socket for the lifetime of the
+    /// connection, returning `None` when `max_connections` has already
+    /// been reached.
+    pub(crate) fn try_acquire(&self) -> Option<ConnectionPermit> {
+        try_increment(&self.active, self.max_connections).map(|()| ConnectionPermit {
+            active: self.active.clone(),
+        })
+    }
+
+    /// Reserve a pre-logon slot, separate from and tighter than
+    /// `max_connections`. Hold this only until `first_msg` resolves
+    /// (first message read, deserialize error, I/O error or `logon_timeout`),
+    /// then drop it so the slot is free for the next connecting socket.
+    pub(crate) fn try_acquire_pending_logon(&self) -> Option<PendingLogonPermit> {
+        try_increment(&self.pending_logons, self.max_pending_logons).map(|()| PendingLogonPermit {
+            pending_logons: self.pending_logons.clone(),
+        })
+    }
+}
+
+fn try_increment(counter: &AtomicUsize, limit: usize) -> Option<()> {
+    loop {
+        let current = counter.load(Ordering::Acquire);
+        if limit != 0 && current >= limit {
+            return None;
+        }
+        if counter
+            .compare_exchange_weak(current, current + 1, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            return Some(());
+        }
+    }
+}
+
+/// Held for the lifetime of one accepted connection, pre-logon or
+/// established; releases its slot back to the [`ConnectionLimiter`] on
+/// drop regardless of how the connection ends.
+#[derive(Debug)]
+pub(crate) struct ConnectionPermit {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Held only until a connection's first message (the `Logon`) resolves
+/// one way or another; dropping it early, as soon as the first message is
+/// read, is what keeps a slow-loris flood from pinning every pre-logon
+/// slot for the full `logon_timeout`.
+#[derive(Debug)]
+pub(crate) struct PendingLogonPermit {
+    pending_logons: Arc<AtomicUsize>,
+}
+
+impl Drop for PendingLogonPermit {
+    fn drop(&mut self) {
+        self.pending_logons.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_max_connections_at_the_boundary() {
+        let limiter = ConnectionLimiter::new(2, 0);
+        let _first = limiter.try_acquire().unwrap();
+        let _second = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().is_none());
+    }
+
+    #[test]
+    fn zero_max_connections_is_unlimited() {
+        let limiter = ConnectionLimiter::new(0, 0);
+        let _permits: Vec<_> = (0..1000).map(|_| limiter.try_acquire().unwrap()).collect();
+    }
+
+    #[test]
+    fn dropping_a_permit_frees_its_slot() {
+        let limiter = ConnectionLimiter::new(1, 0);
+        let permit = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().is_none());
+        drop(permit);
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn pending_logon_budget_is_independent_of_max_connections() {
+        let limiter = ConnectionLimiter::new(10, 1);
+        let _connection = limiter.try_acquire().unwrap();
+        let _pending = limiter.try_acquire_pending_logon().unwrap();
+        // The pending-logon budget is exhausted even though max_connections
+        // has plenty of headroom left.
+        assert!(limiter.try_acquire_pending_logon().is_none());
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn dropping_a_pending_logon_permit_frees_its_slot() {
+        let limiter = ConnectionLimiter::new(0, 1);
+        let pending = limiter.try_acquire_pending_logon().unwrap();
+        assert!(limiter.try_acquire_pending_logon().is_none());
+        drop(pending);
+        assert!(limiter.try_acquire_pending_logon().is_some());
+    }
+}
@@ -2,16 +2,17 @@ use std::{
     cell::RefCell,
     collections::{hash_map::Entry, HashMap},
     rc::Rc,
+    sync::RwLock,
 };
 
 use easyfix_messages::messages::{FixtMessage, Message};
 use futures_util::{pin_mut, Stream};
-use once_cell::unsync::Lazy;
+use once_cell::sync::OnceCell;
 use tokio::{
-    io::{AsyncWrite, AsyncWriteExt},
+    io::{self, AsyncWrite, AsyncWriteExt},
     net::TcpStream,
     sync::mpsc,
-    time::{timeout, Duration},
+    time::{sleep, timeout, Duration},
 };
 use tokio_stream::StreamExt;
 use tracing::{debug, error, info, info_span, Instrument};
@@ -33,33 +34,71 @@ use input_stream::{input_stream, InputEvent};
 mod output_stream;
 use output_stream::{output_stream, OutputEvent};
 
-pub struct Disconnect;
+mod transport;
 
-// TODO: cfg(mt) on mt build
-static mut SENDERS: Lazy<HashMap<SessionId, Sender>> = Lazy::new(HashMap::new);
+mod reconnect;
+pub use reconnect::ReconnectStrategy;
 
-fn senders() -> &'static HashMap<SessionId, Sender> {
-    Lazy::force(unsafe { &SENDERS })
-}
+mod shutdown;
+pub use shutdown::Shutdown;
+use shutdown::ShutdownSignal;
+
+mod admission;
+pub(crate) use admission::ConnectionLimiter;
 
-fn senders_mut() -> &'static mut HashMap<SessionId, Sender> {
-    Lazy::force_mut(unsafe { &mut SENDERS })
+pub struct Disconnect;
+
+// Behind an `RwLock` rather than `static mut` so `register_sender`,
+// `unregister_sender` and `sender` are safe to call from any reactor
+// thread, which is a prerequisite for running sessions on a
+// multi-threaded Tokio runtime. This only makes it safe to push
+// messages *into* a session from another worker thread via `Sender`;
+// `Session`/`State` themselves stay `Rc`-based and must still be driven
+// from the single task/thread that owns that session's connection.
+static SENDERS: OnceCell<RwLock<HashMap<SessionId, Sender>>> = OnceCell::new();
+
+// `Sender` must be `Send + Sync` to live in this `static`'s `HashMap` at
+// all; asserted here so a future change that makes it borrow anything
+// `Rc`-based fails to compile at this call site instead of silently
+// losing thread-safety.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Sender>();
+};
+
+fn senders() -> &'static RwLock<HashMap<SessionId, Sender>> {
+    SENDERS.get_or_init(|| RwLock::new(HashMap::new()))
 }
 
 pub fn register_sender(session_id: SessionId, sender: Sender) {
-    if let Entry::Vacant(entry) = senders_mut().entry(session_id) {
+    let mut senders = senders().write().expect("senders registry lock poisoned");
+    if let Entry::Vacant(entry) = senders.entry(session_id) {
         entry.insert(sender);
     }
 }
 
-pub fn unregister_sender(session_id: &SessionId) {
-    if senders_mut().remove(session_id).is_none() {
-        // TODO: ERROR?
-    }
+/// Removes `session_id`'s sender, returning whether this call was the
+/// one that actually removed it.
+///
+/// The backing `RwLock` makes that `true`/`false` a race-free handoff:
+/// when a session's teardown can be triggered from two places at once
+/// (its own connection loop finishing, and `shutdown_all_sessions`
+/// forcing it after the grace period), only the caller that gets `true`
+/// may proceed to call `on_disconnect` on it, so it runs exactly once.
+pub fn unregister_sender(session_id: &SessionId) -> bool {
+    senders()
+        .write()
+        .expect("senders registry lock poisoned")
+        .remove(session_id)
+        .is_some()
 }
 
-pub fn sender(session_id: &SessionId) -> Option<&Sender> {
-    senders().get(session_id)
+pub fn sender(session_id: &SessionId) -> Option<Sender> {
+    senders()
+        .read()
+        .expect("senders registry lock poisoned")
+        .get(session_id)
+        .cloned()
 }
 
 // TODO: Remove?
@@ -81,6 +120,51 @@ pub fn send_raw(msg: Box<FixtMessage>) -> Result<(), Box<FixtMessage>> {
     }
 }
 
+/// Gracefully shut down every session tracked in `active_sessions`.
+///
+/// Sends a `Logout` through each session's registered [`Sender`] and
+/// waits up to `grace_period` for `active_sessions` to empty (each
+/// session's connection task removes it from `active_sessions` once its
+/// input and output loops have both finished). Any session still
+/// present once the grace period elapses is forced to disconnect.
+pub(crate) async fn shutdown_all_sessions<S>(
+    active_sessions: &Rc<RefCell<ActiveSessionsMap<S>>>,
+    grace_period: Duration,
+) where
+    S: MessagesStorage,
+{
+    let session_ids: Vec<SessionId> = active_sessions.borrow().keys().cloned().collect();
+    if session_ids.is_empty() {
+        return;
+    }
+    info!(
+        "shutdown requested, logging out {} session(s)",
+        session_ids.len()
+    );
+    for session_id in &session_ids {
+        if let Some(session) = active_sessions.borrow().get(session_id).cloned() {
+            session.disconnect(DisconnectReason::Shutdown);
+        }
+    }
+
+    let deadline = tokio::time::Instant::now() + grace_period;
+    while !active_sessions.borrow().is_empty() && tokio::time::Instant::now() < deadline {
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    for session_id in &session_ids {
+        // Only the winner of the `unregister_sender` race tears the
+        // session down: if its own connection task got there first,
+        // `on_disconnect` has already run and must not run again here.
+        if unregister_sender(session_id) {
+            if let Some(session) = active_sessions.borrow_mut().remove(session_id) {
+                info!(%session_id, "grace period elapsed, forcing disconnect");
+                session.on_disconnect().await;
+            }
+        }
+    }
+}
+
 async fn first_msg(
     stream: &mut (impl Stream<Item = InputEvent> + Unpin),
     logon_timeout: Duration,
@@ -107,16 +191,37 @@ pub(crate) async fn acceptor_connection<S>(
     sessions: Rc<RefCell<SessionsMap<S>>>,
     active_sessions: Rc<RefCell<ActiveSessionsMap<S>>>,
     emitter: Emitter,
+    connection_limiter: ConnectionLimiter,
 ) -> Result<(), Error>
 where
     S: MessagesStorage,
 {
-    let (source, sink) = tcp_stream.into_split();
+    // Admission control: refuse the connection outright once
+    // `max_connections` is reached, rather than accepting sockets we have
+    // no intention of servicing. This permit is held for the lifetime of
+    // the connection, established or not.
+    let _permit = connection_limiter
+        .try_acquire()
+        .ok_or(Error::SessionError(SessionError::ConnectionLimitExceeded))?;
+    // A second, tighter cap on sockets that haven't logged on yet, held
+    // only until `first_msg` resolves, so a flood of sockets that never
+    // send a `Logon` can occupy at most `max_pending_logons` slots for up
+    // to `logon_timeout` each, instead of every `max_connections` slot.
+    let pending_logon_permit = connection_limiter
+        .try_acquire_pending_logon()
+        .ok_or(Error::SessionError(SessionError::ConnectionLimitExceeded))?;
+
+    let transport = transport::accept(tcp_stream, settings.tls_acceptor.clone()).await?;
+    let (source, sink) = io::split(transport);
     let stream = input_stream(source);
     let logon_timeout =
         settings.auto_disconnect_after_no_logon_received + NO_INBOUND_TIMEOUT_PADDING;
     pin_mut!(stream);
     let msg = first_msg(&mut stream, logon_timeout).await?;
+    // A first message arrived in time: the pre-logon slot goes back to the
+    // next connecting socket here, before the message is checked to be a
+    // `Logon` for a known session.
+    drop(pending_logon_permit);
     let session_id = SessionId::from_input_msg(&msg);
     debug!("first_msg: {msg:?}");
 
@@ -176,24 +281,53 @@ where
     );
     info!("connection closed");
     // TODO: error here?
-    connection.session.on_disconnect().await;
-    unregister_sender(&session_id);
-    active_sessions.borrow_mut().remove(&session_id);
+    // Only the winner of this race actually tears the session down; see
+    // `unregister_sender`'s doc comment for why (`shutdown_all_sessions`
+    // can force the same session concurrently after its grace period).
+    if unregister_sender(&session_id) {
+        connection.session.on_disconnect().await;
+        active_sessions.borrow_mut().remove(&session_id);
+    }
     ret.map(|_| ())
 }
 
-pub(crate) async fn initiator_connection<S>(
+/// Outcome of one initiator connection attempt.
+struct ConnectAttemptOutcome<S> {
+    /// Why the connection ended.
+    reason: DisconnectReason,
+    /// Whether `State` reports the session as logged on when this
+    /// attempt's run ends, checked before `on_disconnect` tears it back down.
+    /// This is the signal [`initiator_connection`] resets its backoff
+    /// counter on, distinct from merely reaching the end of the run.
+    logon_succeeded: bool,
+    /// The session this attempt ran, so `initiator_connection` can call
+    /// `on_disconnect` on it once the reconnect loop is done retrying.
+    session: Rc<Session<S>>,
+    /// Whether this attempt won the `unregister_sender` race and so is
+    /// the one actually allowed to call `on_disconnect` on `session`
+    /// (see `unregister_sender`'s doc comment).
+    teardown_owner: bool,
+}
+
+/// Connect and run a single initiator session over one TCP connection.
+///
+/// Returns the reason the connection ended, which
+/// [`initiator_connection`] uses to decide whether, and how long, to
+/// wait before re-dialing.
+async fn connect_and_run_initiator<S>(
     tcp_stream: TcpStream,
     settings: Settings,
     session_settings: SessionSettings,
     state: Rc<RefCell<State<S>>>,
     active_sessions: Rc<RefCell<ActiveSessionsMap<S>>>,
     emitter: Emitter,
-) -> Result<(), Error>
+    mut shutdown: ShutdownSignal,
+) -> Result<ConnectAttemptOutcome<S>, Error>
 where
     S: MessagesStorage,
 {
-    let (source, sink) = tcp_stream.into_split();
+    let transport = transport::connect(tcp_stream, session_settings.tls_connector.clone()).await?;
+    let (source, sink) = io::split(transport);
     let session_id = session_settings.session_id.clone();
 
     let (sender, receiver) = mpsc::unbounded_channel();
@@ -203,7 +337,7 @@ where
     let session = Rc::new(Session::new(
         settings,
         session_settings,
-        state,
+        state.clone(),
         sender,
         emitter.clone(),
     ));
@@ -219,11 +353,6 @@ where
     let input_loop_span = info_span!(parent: &session_span, "in");
     let output_loop_span = info_span!(parent: &session_span, "out");
 
-    // TODO: Not here!, send this event when SessionState is created!
-    emitter
-        .send(FixEventInternal::Created(session_id.clone()))
-        .await;
-
     let input_stream = input_stream(source)
         .timeout(session.heartbeat_interval() + NO_INBOUND_TIMEOUT_PADDING)
         .map(|res| res.unwrap_or(InputEvent::Timeout));
@@ -238,20 +367,164 @@ where
 
     let connection = Connection::new(session);
 
-    let ret = tokio::try_join!(
-        connection
-            .input_loop(input_stream)
-            .instrument(input_loop_span),
-        connection
-            .output_loop(sink, output_stream)
-            .instrument(output_loop_span),
-    );
+    let mut run = Box::pin(async {
+        tokio::try_join!(
+            connection
+                .input_loop(input_stream)
+                .instrument(input_loop_span),
+            connection
+                .output_loop(sink, output_stream)
+                .instrument(output_loop_span),
+        )
+    });
+
+    // A healthy, logged-on session otherwise only notices a shutdown
+    // request the next time the reconnect loop parks in its backoff
+    // sleep; select on it here too so a *live* initiator actually stops
+    // instead of running until its next unrelated disconnect.
+    let ret = tokio::select! {
+        ret = &mut run => ret,
+        _ = shutdown.requested() => {
+            info!("shutdown requested, disconnecting live initiator session");
+            connection.session.disconnect(DisconnectReason::Shutdown);
+            run.await
+        }
+    };
     info!("connection closed");
-    // TODO: error here?
-    connection.session.on_disconnect().await;
-    unregister_sender(&session_id);
-    active_sessions.borrow_mut().remove(&session_id);
-    ret.map(|_| ())
+    // Checked before `on_disconnect` runs, since that's what tears the
+    // logged-on state in `State` back down.
+    let logon_succeeded = state.borrow().is_logged_on();
+    // Registry/active-sessions bookkeeping happens on every attempt so
+    // the next reconnect can re-register its own sender. `on_disconnect`
+    // itself is NOT called here: it may reset protocol state that's
+    // supposed to survive a redial (persisted seqnums in `State`), so
+    // it's deferred to `initiator_connection`, which invokes it at most
+    // once, only on the attempt that ends the whole reconnect loop.
+    let teardown_owner = unregister_sender(&session_id);
+    if teardown_owner {
+        active_sessions.borrow_mut().remove(&session_id);
+    }
+    ret.map(|(_, reason)| ConnectAttemptOutcome {
+        reason,
+        logon_succeeded,
+        session: connection.session.clone(),
+        teardown_owner,
+    })
+}
+
+/// Calls `on_disconnect` on the outcome's session, but only if this
+/// attempt won the `unregister_sender` teardown race — see
+/// [`ConnectAttemptOutcome::teardown_owner`].
+async fn finish_initiator_loop<S>(
+    result: Result<ConnectAttemptOutcome<S>, Error>,
+) -> Result<(), Error>
+where
+    S: MessagesStorage,
+{
+    if let Ok(outcome) = &result {
+        if outcome.teardown_owner {
+            outcome.session.on_disconnect().await;
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Run an initiator session against `connect_addr`, automatically
+/// re-dialing according to `session_settings.reconnect_strategy` when the
+/// connection is lost.
+///
+/// The sequence numbers persisted in `state` survive across reconnects,
+/// so resend requests keep working after a redial: `on_disconnect` (which
+/// may reset protocol state) only ever runs once, on the attempt that
+/// ends the whole loop, never on an intermediate reconnect.
+pub(crate) async fn initiator_connection<S>(
+    connect_addr: std::net::SocketAddr,
+    settings: Settings,
+    session_settings: SessionSettings,
+    state: Rc<RefCell<State<S>>>,
+    active_sessions: Rc<RefCell<ActiveSessionsMap<S>>>,
+    emitter: Emitter,
+    mut shutdown: ShutdownSignal,
+) -> Result<(), Error>
+where
+    S: MessagesStorage,
+{
+    let reconnect_strategy = session_settings.reconnect_strategy.clone();
+    let mut attempt: u32 = 0;
+
+    // Emitted once per logical session, not per reconnect attempt: the
+    // `State` behind it persists across redials, so from the
+    // application's point of view this is one session being created, not
+    // one per TCP connection.
+    // TODO: Not here!, send this event when SessionState is created!
+    emitter
+        .send(FixEventInternal::Created(session_settings.session_id.clone()))
+        .await;
+
+    loop {
+        // The dial itself is part of the retriable path: a peer that's
+        // simply down when we try to connect is an I/O failure like any
+        // other, and must go through the same backoff as a connection
+        // lost mid-session.
+        let result = match TcpStream::connect(connect_addr).await {
+            Ok(tcp_stream) => {
+                connect_and_run_initiator(
+                    tcp_stream,
+                    settings.clone(),
+                    session_settings.clone(),
+                    state.clone(),
+                    active_sessions.clone(),
+                    emitter.clone(),
+                    shutdown.clone(),
+                )
+                .await
+            }
+            Err(error) => Err(error.into()),
+        };
+
+        let should_retry = match &result {
+            Ok(outcome) => match outcome.reason {
+                DisconnectReason::Shutdown => {
+                    info!("shutdown requested, not reconnecting");
+                    false
+                }
+                DisconnectReason::ConnectionLost => true,
+                _ => {
+                    info!("initiator session ended cleanly, not reconnecting");
+                    false
+                }
+            },
+            Err(error) => {
+                error!("initiator connection attempt failed: {error}");
+                true
+            }
+        };
+
+        // Only a completed logon resets the backoff counter: a peer that
+        // accepts the TCP connection but never logs us on must keep
+        // climbing the backoff curve, not get pinned at `delay(0)`.
+        if matches!(&result, Ok(outcome) if outcome.logon_succeeded) {
+            attempt = 0;
+        }
+
+        if !should_retry {
+            return finish_initiator_loop(result).await;
+        }
+
+        let Some(delay) = reconnect_strategy.delay(attempt) else {
+            return finish_initiator_loop(result).await;
+        };
+        attempt += 1;
+        info!("reconnecting in {delay:?} (attempt {attempt})");
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = shutdown.requested() => {
+                info!("shutdown requested during reconnect backoff");
+                let _ = finish_initiator_loop(result).await;
+                return Ok(());
+            }
+        }
+    }
 }
 
 impl<S: MessagesStorage> Connection<S> {
@@ -285,12 +558,16 @@ impl<S: MessagesStorage> Connection<S> {
         &self,
         mut sink: impl AsyncWrite + Unpin,
         mut output_stream: impl Stream<Item = OutputEvent> + Unpin,
-    ) -> Result<(), Error> {
+    ) -> Result<DisconnectReason, Error> {
         while let Some(event) = output_stream.next().await {
             match event {
                 OutputEvent::Message(msg) => {
                     if let Err(error) = sink.write_all(&msg).await {
-                        return self.session.on_io_error(error).await;
+                        return self
+                            .session
+                            .on_io_error(error)
+                            .await
+                            .map(|_| DisconnectReason::ConnectionLost);
                     }
                 }
                 OutputEvent::Timeout => self.session.on_out_timeout().await,
@@ -301,15 +578,15 @@ impl<S: MessagesStorage> Connection<S> {
                     // XXX: Emit logout here instead of Session::disconnect,
                     //      so `Logout` event will be delivered after Logout
                     //      message instead of randomly before or after.
-                    self.session.emit_logout(reason).await;
+                    self.session.emit_logout(reason.clone()).await;
                     info!("disconnect, exit output processing");
-                    return Ok(());
+                    return Ok(reason);
                 }
             }
         }
         self.session
             .emit_logout(DisconnectReason::ConnectionLost)
             .await;
-        Ok(())
+        Ok(DisconnectReason::ConnectionLost)
     }
 }
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Reconnection policy for an initiator session: how long to wait before
+/// re-dialing after the TCP connection is lost.
+#[derive(Debug, Clone)]
+pub enum ReconnectStrategy {
+    /// Never reconnect automatically; report the disconnect to the caller.
+    None,
+    /// Always wait the same amount of time between attempts.
+    FixedInterval(Duration),
+    /// Wait `initial * multiplier^attempt`, capped at `max`.
+    ExponentialBackoff {
+        initial: Duration,
+        max: Duration,
+        multiplier: f64,
+    },
+}
+
+impl Default for ReconnectStrategy {
+    fn default() -> Self {
+        ReconnectStrategy::None
+    }
+}
+
+impl ReconnectStrategy {
+    /// Delay before the `attempt`-th reconnect (0-based), with ±10%
+    /// jitter applied so a fleet of initiators losing connectivity at
+    /// the same time doesn't all redial in lock-step.
+    pub(crate) fn delay(&self, attempt: u32) -> Option<Duration> {
+        let base = match self {
+            ReconnectStrategy::None => return None,
+            ReconnectStrategy::FixedInterval(interval) => *interval,
+            ReconnectStrategy::ExponentialBackoff {
+                initial,
+                max,
+                multiplier,
+            } => {
+                let scaled = initial.as_secs_f64() * multiplier.powi(attempt as i32);
+                Duration::from_secs_f64(scaled.min(max.as_secs_f64()))
+            }
+        };
+        Some(jitter(base))
+    }
+}
+
+fn jitter(duration: Duration) -> Duration {
+    let factor = rand::thread_rng().gen_range(0.9..=1.1);
+    Duration::from_secs_f64((duration.as_secs_f64() * factor).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_within_jitter(actual: Duration, base: Duration) {
+        let lower = base.as_secs_f64() * 0.9;
+        let upper = base.as_secs_f64() * 1.1;
+        let actual = actual.as_secs_f64();
+        assert!(
+            (lower..=upper).contains(&actual),
+            "{actual} not within ±10% of {base:?}"
+        );
+    }
+
+    #[test]
+    fn none_never_reconnects() {
+        assert!(ReconnectStrategy::None.delay(0).is_none());
+        assert!(ReconnectStrategy::None.delay(100).is_none());
+    }
+
+    #[test]
+    fn fixed_interval_ignores_attempt() {
+        let strategy = ReconnectStrategy::FixedInterval(Duration::from_secs(5));
+        for attempt in [0, 1, 10, 100] {
+            let delay = strategy.delay(attempt).unwrap();
+            assert_within_jitter(delay, Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn exponential_backoff_grows_then_caps_at_max() {
+        let strategy = ReconnectStrategy::ExponentialBackoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(10),
+            multiplier: 2.0,
+        };
+        // 1, 2, 4, 8, then capped at 10 from here on.
+        assert_within_jitter(strategy.delay(0).unwrap(), Duration::from_secs(1));
+        assert_within_jitter(strategy.delay(1).unwrap(), Duration::from_secs(2));
+        assert_within_jitter(strategy.delay(2).unwrap(), Duration::from_secs(4));
+        assert_within_jitter(strategy.delay(3).unwrap(), Duration::from_secs(8));
+        assert_within_jitter(strategy.delay(4).unwrap(), Duration::from_secs(10));
+        assert_within_jitter(strategy.delay(20).unwrap(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn jitter_stays_within_ten_percent() {
+        let base = Duration::from_secs(3);
+        for _ in 0..1000 {
+            assert_within_jitter(jitter(base), base);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use tokio::sync::watch;
+
+/// Handle returned alongside an acceptor/initiator startup, used to
+/// request a coordinated shutdown of every session the engine is
+/// currently running.
+///
+/// Dropping the handle does not trigger a shutdown; call [`Shutdown::trigger`]
+/// explicitly, e.g. from a Ctrl-C/SIGTERM handler.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    tx: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> (Shutdown, ShutdownSignal) {
+        let (tx, rx) = watch::channel(false);
+        (Shutdown { tx }, ShutdownSignal { rx })
+    }
+
+    /// Request that every active session log out and the engine stop.
+    pub fn trigger(&self) {
+        // A send error only happens once every `ShutdownSignal` has
+        // already been dropped, i.e. there is nothing left to shut down.
+        let _ = self.tx.send(true);
+    }
+}
+
+/// Cloned into each connection task so it can observe a shutdown request
+/// raised through the paired [`Shutdown`] handle.
+#[derive(Debug, Clone)]
+pub(crate) struct ShutdownSignal {
+    rx: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    /// Resolves once [`Shutdown::trigger`] has been called.
+    pub(crate) async fn requested(&mut self) {
+        while !*self.rx.borrow() {
+            if self.rx.changed().await.is_err() {
+                // Every `Shutdown` handle was dropped without a trigger,
+                // so no shutdown can be requested any more.
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
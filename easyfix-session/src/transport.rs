@@ -0,0 +1,107 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{client::TlsStream as ClientTlsStream, server::TlsStream as ServerTlsStream};
+
+use crate::Error;
+
+/// Transport used for a FIX connection: a plain TCP socket, or a
+/// TLS-wrapped one for FIX-over-TLS (FIXS) sessions.
+///
+/// Boxed `TlsStream`s keep the enum small, since `acceptor_connection`
+/// and `initiator_connection` only ever hold one `Transport` at a time.
+#[derive(Debug)]
+pub(crate) enum Transport {
+    Tcp(TcpStream),
+    TlsAcceptor(Box<ServerTlsStream<TcpStream>>),
+    TlsInitiator(Box<ClientTlsStream<TcpStream>>),
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::TlsAcceptor(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            Transport::TlsInitiator(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::TlsAcceptor(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            Transport::TlsInitiator(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::TlsAcceptor(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            Transport::TlsInitiator(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::TlsAcceptor(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+            Transport::TlsInitiator(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Run the server-side TLS handshake on an inbound connection when FIXS
+/// is configured for the acceptor, otherwise pass the socket through
+/// unchanged.
+pub(crate) async fn accept(
+    tcp_stream: TcpStream,
+    tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
+) -> Result<Transport, Error> {
+    match tls_acceptor {
+        Some(tls_acceptor) => {
+            let tls_stream = tls_acceptor.accept(tcp_stream).await.map_err(Error::from)?;
+            Ok(Transport::TlsAcceptor(Box::new(tls_stream)))
+        }
+        None => Ok(Transport::Tcp(tcp_stream)),
+    }
+}
+
+/// Run the client-side TLS handshake on an outbound connection when FIXS
+/// is configured for the initiator, otherwise pass the socket through
+/// unchanged.
+pub(crate) async fn connect(
+    tcp_stream: TcpStream,
+    tls_connector: Option<(
+        tokio_rustls::TlsConnector,
+        tokio_rustls::rustls::pki_types::ServerName<'static>,
+    )>,
+) -> Result<Transport, Error> {
+    match tls_connector {
+        Some((tls_connector, server_name)) => {
+            let tls_stream = tls_connector
+                .connect(server_name, tcp_stream)
+                .await
+                .map_err(Error::from)?;
+            Ok(Transport::TlsInitiator(Box::new(tls_stream)))
+        }
+        None => Ok(Transport::Tcp(tcp_stream)),
+    }
+}